@@ -0,0 +1,581 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! A small signed HTTP client for the AWS "Query" protocol (EC2,
+//! Autoscaling) and the S3 REST API, replacing the generated rusoto
+//! clients. It does just enough to sign and send requests and to pull the
+//! handful of fields this crate actually cares about out of the XML
+//! responses.
+
+use crate::aws::encoding::percent_encode;
+use crate::aws::{credentials, imds, sigv4};
+use anyhow::{format_err, Result};
+use libra_logger::warn;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::time::SystemTime;
+
+/// S3 requires payload hashes for streamed uploads too, but computing one
+/// would mean buffering the whole body first. S3 (uniquely among the
+/// services we sign for) accepts this literal in place of a real hash.
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// A signed client for one AWS service in one region.
+pub struct AwsClient {
+    http: reqwest::blocking::Client,
+    credentials: sigv4::Credentials,
+    region: String,
+}
+
+impl AwsClient {
+    pub fn new(region: &str) -> Result<Self> {
+        Ok(Self {
+            http: reqwest::blocking::Client::new(),
+            credentials: credentials::from_env_or_instance_profile()?,
+            region: region.to_string(),
+        })
+    }
+
+    pub fn with_credentials(region: &str, credentials: sigv4::Credentials) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            credentials,
+            region: region.to_string(),
+        }
+    }
+
+    /// Like `new`, but tolerates failing to resolve credentials instead of
+    /// returning an error, falling back to empty placeholder credentials
+    /// that simply won't authenticate any request actually made with them.
+    /// Used to build the EC2/ECR/ECS clients `Aws` always carries even on
+    /// the k8s discovery path, where a pod may have no static `AWS_*` env
+    /// vars and no reachable instance metadata service, but also has no
+    /// need to ever issue an EC2/ECR/ECS request through this client.
+    pub fn new_best_effort(region: &str) -> Self {
+        let credentials = credentials::from_env_or_instance_profile().unwrap_or_else(|e| {
+            warn!(
+                "failed to resolve AWS credentials for EC2/ECR/ECS client, \
+                 continuing without them: {}",
+                e
+            );
+            sigv4::Credentials {
+                access_key_id: String::new(),
+                secret_access_key: String::new(),
+                session_token: None,
+            }
+        });
+        Self {
+            http: reqwest::blocking::Client::new(),
+            credentials,
+            region: region.to_string(),
+        }
+    }
+
+    /// Issue a signed `Action`-style query request (used by EC2 and
+    /// Autoscaling) and return the raw XML response body.
+    pub fn query(&self, service: &str, params: &[(&str, &str)]) -> Result<String> {
+        let host = format!("{}.{}.amazonaws.com", service, self.region);
+        let url = format!("https://{}/", host);
+        let body = params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, percent_encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let amz_date = amz_date_now();
+        let extra_headers = vec![(
+            "content-type".to_string(),
+            "application/x-www-form-urlencoded; charset=utf-8".to_string(),
+        )];
+        let signed = sigv4::sign(
+            &self.credentials,
+            &self.region,
+            service,
+            "POST",
+            &host,
+            "/",
+            &BTreeMap::new(),
+            &extra_headers,
+            body.as_bytes(),
+            &amz_date,
+        );
+
+        let mut request = self
+            .http
+            .post(&url)
+            .header(
+                "content-type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            )
+            .body(body);
+        for (name, value) in &signed.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let response = request
+            .send()
+            .map_err(|e| format_err!("{} request failed: {}", service, e))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .map_err(|e| format_err!("failed to read {} response: {}", service, e))?;
+        if !status.is_success() {
+            return Err(format_err!(
+                "{} request returned {}: {}",
+                service,
+                status,
+                text
+            ));
+        }
+        Ok(text)
+    }
+
+    /// Issue `query` repeatedly, following the AWS Query protocol's
+    /// `NextToken` pagination field until a response doesn't return one,
+    /// returning every page's raw XML body in order.
+    pub fn query_paginated(&self, service: &str, params: &[(&str, &str)]) -> Result<Vec<String>> {
+        let mut pages = Vec::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let mut request_params = params.to_vec();
+            if let Some(token) = &next_token {
+                request_params.push(("NextToken", token.as_str()));
+            }
+            let page = self.query(service, &request_params)?;
+            // Autoscaling spells this `NextToken`; EC2 spells it `nextToken`.
+            // A present-but-empty element (`<NextToken></NextToken>`) still
+            // means "no more pages", not "the token is the empty string".
+            next_token = xml_field(&page, "NextToken")
+                .or_else(|| xml_field(&page, "nextToken"))
+                .filter(|token| !token.is_empty());
+            pages.push(page);
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok(pages)
+    }
+
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    pub fn credentials(&self) -> &sigv4::Credentials {
+        &self.credentials
+    }
+
+    pub fn http(&self) -> &reqwest::blocking::Client {
+        &self.http
+    }
+}
+
+pub fn amz_date_now() -> String {
+    // Query-protocol services and S3 both just need an ISO-8601 basic format
+    // timestamp; we derive it from the system clock rather than pulling in
+    // a date/time crate purely for formatting.
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("system clock is before the epoch");
+    httpdate_to_amz(now.as_secs())
+}
+
+/// Turn a unix timestamp into `YYYYMMDDTHHMMSSZ`.
+fn httpdate_to_amz(secs: u64) -> String {
+    let days = secs / 86_400;
+    let time_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        (time_of_day % 3600) / 60,
+        time_of_day % 60
+    )
+}
+
+/// Howard Hinnant's `civil_from_days` algorithm, converting a day count
+/// since the epoch into a (year, month, day) triple without needing a date
+/// library.
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = (z - era * 146_097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+/// Pull the text content of the first `<tag>...</tag>` found in `xml`.
+pub fn xml_field(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some(xml[start..end].to_string())
+}
+
+/// Split `xml` into the text of every top-level `<tag>...</tag>` block.
+/// AWS Query responses reuse the same element name at multiple nesting
+/// depths (EC2's `DescribeInstances` nests `item` inside `item` for
+/// reservations/instances/tags; Autoscaling's `DescribeAutoScalingGroups`
+/// does the same with `member`), so this tracks nesting depth to find each
+/// top-level block's actual matching close tag rather than just its nearest
+/// one.
+pub fn xml_blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut blocks = Vec::new();
+    let mut search_from = 0;
+    while let Some(rel_start) = xml[search_from..].find(&open) {
+        let start = search_from + rel_start + open.len();
+        let mut depth = 1;
+        let mut pos = start;
+        let end = loop {
+            let next_open = xml[pos..].find(&open).map(|i| pos + i);
+            let next_close = xml[pos..].find(&close).map(|i| pos + i);
+            match (next_open, next_close) {
+                (Some(o), Some(c)) if o < c => {
+                    depth += 1;
+                    pos = o + open.len();
+                }
+                (_, Some(c)) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break c;
+                    }
+                    pos = c + close.len();
+                }
+                _ => return blocks,
+            }
+        };
+        blocks.push(&xml[start..end]);
+        search_from = end + close.len();
+    }
+    blocks
+}
+
+/// Build the query string for a request URL the same way `sigv4::sign`
+/// builds the canonical query string it signs, so the percent-encoding of
+/// values like a `/`-or-`+`-bearing S3 `UploadId` can't drift between what
+/// was signed and what's actually sent on the wire.
+fn canonical_query_string(query_params: &BTreeMap<String, String>) -> String {
+    query_params
+        .iter()
+        .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// `PUT` an object to S3 in a single request, streaming `file` as the body
+/// instead of buffering it into memory. Since the payload is never read up
+/// front, it is signed with the `UNSIGNED-PAYLOAD` placeholder S3 accepts in
+/// place of a real content hash.
+pub fn s3_put_object(
+    client: &AwsClient,
+    bucket: &str,
+    key: &str,
+    file: File,
+    size: u64,
+    content_type: Option<String>,
+) -> Result<()> {
+    let host = format!("{}.s3.{}.amazonaws.com", bucket, client.region());
+    let url = format!("https://{}/{}", host, key);
+    let amz_date = amz_date_now();
+    let mut extra_headers = vec![("content-length".to_string(), size.to_string())];
+    if let Some(content_type) = &content_type {
+        extra_headers.push(("content-type".to_string(), content_type.clone()));
+    }
+    let signed = sigv4::sign_with_payload_hash(
+        client.credentials(),
+        client.region(),
+        "s3",
+        "PUT",
+        &host,
+        &format!("/{}", key),
+        &BTreeMap::new(),
+        &extra_headers,
+        UNSIGNED_PAYLOAD,
+        &amz_date,
+    );
+
+    let mut request = client
+        .http()
+        .put(&url)
+        .header("content-length", size)
+        .body(file);
+    if let Some(content_type) = content_type {
+        request = request.header("content-type", content_type);
+    }
+    for (name, value) in &signed.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request
+        .send()
+        .map_err(|e| format_err!("PutObject request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        return Err(format_err!("PutObject returned {}: {}", status, text));
+    }
+    Ok(())
+}
+
+/// Start a multipart upload, returning the `UploadId` the rest of the
+/// `s3_*_multipart_upload` calls need.
+pub fn s3_create_multipart_upload(
+    client: &AwsClient,
+    bucket: &str,
+    key: &str,
+    content_type: Option<String>,
+) -> Result<String> {
+    let host = format!("{}.s3.{}.amazonaws.com", bucket, client.region());
+    let url = format!("https://{}/{}?uploads", host, key);
+    let amz_date = amz_date_now();
+    let mut query_params = BTreeMap::new();
+    query_params.insert("uploads".to_string(), "".to_string());
+    let mut extra_headers = Vec::new();
+    if let Some(content_type) = &content_type {
+        extra_headers.push(("content-type".to_string(), content_type.clone()));
+    }
+    let signed = sigv4::sign(
+        client.credentials(),
+        client.region(),
+        "s3",
+        "POST",
+        &host,
+        &format!("/{}", key),
+        &query_params,
+        &extra_headers,
+        b"",
+        &amz_date,
+    );
+
+    let mut request = client.http().post(&url);
+    if let Some(content_type) = content_type {
+        request = request.header("content-type", content_type);
+    }
+    for (name, value) in &signed.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request
+        .send()
+        .map_err(|e| format_err!("CreateMultipartUpload request failed: {}", e))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .map_err(|e| format_err!("failed to read CreateMultipartUpload response: {}", e))?;
+    if !status.is_success() {
+        return Err(format_err!(
+            "CreateMultipartUpload returned {}: {}",
+            status,
+            text
+        ));
+    }
+    xml_field(&text, "UploadId")
+        .ok_or_else(|| format_err!("CreateMultipartUpload response missing UploadId: {}", text))
+}
+
+/// Upload one part of a multipart upload, returning the `ETag` S3 assigned
+/// to it. The caller must hand these back, in order, to
+/// `s3_complete_multipart_upload`.
+pub fn s3_upload_part(
+    client: &AwsClient,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: u32,
+    body: Vec<u8>,
+) -> Result<String> {
+    let host = format!("{}.s3.{}.amazonaws.com", bucket, client.region());
+    let amz_date = amz_date_now();
+    let mut query_params = BTreeMap::new();
+    query_params.insert("partNumber".to_string(), part_number.to_string());
+    query_params.insert("uploadId".to_string(), upload_id.to_string());
+    let url = format!(
+        "https://{}/{}?{}",
+        host,
+        key,
+        canonical_query_string(&query_params)
+    );
+    let signed = sigv4::sign(
+        client.credentials(),
+        client.region(),
+        "s3",
+        "PUT",
+        &host,
+        &format!("/{}", key),
+        &query_params,
+        &[],
+        &body,
+        &amz_date,
+    );
+
+    let mut request = client.http().put(&url).body(body);
+    for (name, value) in &signed.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request
+        .send()
+        .map_err(|e| format_err!("UploadPart request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        return Err(format_err!("UploadPart returned {}: {}", status, text));
+    }
+    response
+        .headers()
+        .get("etag")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .ok_or_else(|| format_err!("UploadPart response missing ETag header"))
+}
+
+/// Finish a multipart upload given the `(part number, ETag)` of every part,
+/// in order.
+pub fn s3_complete_multipart_upload(
+    client: &AwsClient,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    parts: &[(u32, String)],
+) -> Result<()> {
+    let host = format!("{}.s3.{}.amazonaws.com", bucket, client.region());
+    let amz_date = amz_date_now();
+    let mut query_params = BTreeMap::new();
+    query_params.insert("uploadId".to_string(), upload_id.to_string());
+    let url = format!(
+        "https://{}/{}?{}",
+        host,
+        key,
+        canonical_query_string(&query_params)
+    );
+
+    let parts_xml: String = parts
+        .iter()
+        .map(|(part_number, etag)| {
+            format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                part_number, etag
+            )
+        })
+        .collect();
+    let body = format!(
+        "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+        parts_xml
+    );
+
+    let signed = sigv4::sign(
+        client.credentials(),
+        client.region(),
+        "s3",
+        "POST",
+        &host,
+        &format!("/{}", key),
+        &query_params,
+        &[],
+        body.as_bytes(),
+        &amz_date,
+    );
+
+    let mut request = client.http().post(&url).body(body);
+    for (name, value) in &signed.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request
+        .send()
+        .map_err(|e| format_err!("CompleteMultipartUpload request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        return Err(format_err!(
+            "CompleteMultipartUpload returned {}: {}",
+            status,
+            text
+        ));
+    }
+    Ok(())
+}
+
+/// Abort a multipart upload so S3 doesn't keep billing for the parts
+/// already uploaded.
+pub fn s3_abort_multipart_upload(
+    client: &AwsClient,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+) -> Result<()> {
+    let host = format!("{}.s3.{}.amazonaws.com", bucket, client.region());
+    let amz_date = amz_date_now();
+    let mut query_params = BTreeMap::new();
+    query_params.insert("uploadId".to_string(), upload_id.to_string());
+    let url = format!(
+        "https://{}/{}?{}",
+        host,
+        key,
+        canonical_query_string(&query_params)
+    );
+    let signed = sigv4::sign(
+        client.credentials(),
+        client.region(),
+        "s3",
+        "DELETE",
+        &host,
+        &format!("/{}", key),
+        &query_params,
+        &[],
+        b"",
+        &amz_date,
+    );
+
+    let mut request = client.http().delete(&url);
+    for (name, value) in &signed.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request
+        .send()
+        .map_err(|e| format_err!("AbortMultipartUpload request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        return Err(format_err!(
+            "AbortMultipartUpload returned {}: {}",
+            status,
+            text
+        ));
+    }
+    Ok(())
+}
+
+/// Resolve the region via the EC2 instance metadata `placement/region`
+/// endpoint, used as one step of the region resolution precedence chain.
+pub fn region_from_instance_metadata() -> Result<String> {
+    let client = reqwest::blocking::Client::new();
+    imds::get("/latest/meta-data/placement/region", &client)
+}
+
+/// Resolve which region to operate in: an explicit argument wins, then the
+/// usual AWS environment variables, then the EC2 instance metadata, falling
+/// back to `us-west-2` for environments with none of the above (e.g.
+/// developer laptops running against a sandboxed account).
+pub fn resolve_region(explicit: Option<&str>) -> String {
+    if let Some(region) = explicit {
+        return region.to_string();
+    }
+    if let Ok(region) = std::env::var("AWS_REGION") {
+        return region;
+    }
+    if let Ok(region) = std::env::var("AWS_DEFAULT_REGION") {
+        return region;
+    }
+    if let Ok(region) = region_from_instance_metadata() {
+        return region;
+    }
+    DEFAULT_REGION.to_string()
+}
+
+pub const DEFAULT_REGION: &str = "us-west-2";
@@ -0,0 +1,122 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Resolution of AWS credentials, replacing the provider chain that used to
+//! come from `rusoto_credential` / `rusoto_sts`.
+
+use crate::aws::encoding::percent_encode;
+use crate::aws::sigv4::Credentials;
+use anyhow::{format_err, Result};
+use std::env;
+
+/// Resolve credentials the same way the default rusoto provider chain used
+/// to: environment variables first, falling back to the EC2 instance
+/// profile. `autoscale` instead tries the web identity token mounted into
+/// k8s pods (IRSA) first, mirroring `WebIdentityProvider::from_k8s_env`, and
+/// only falls back to this chain when that fails.
+pub fn from_env_or_instance_profile() -> Result<Credentials> {
+    if let Ok(creds) = from_env() {
+        return Ok(creds);
+    }
+    from_instance_profile()
+}
+
+fn from_env() -> Result<Credentials> {
+    let access_key_id =
+        env::var("AWS_ACCESS_KEY_ID").map_err(|_| format_err!("AWS_ACCESS_KEY_ID not set"))?;
+    let secret_access_key = env::var("AWS_SECRET_ACCESS_KEY")
+        .map_err(|_| format_err!("AWS_SECRET_ACCESS_KEY not set"))?;
+    let session_token = env::var("AWS_SESSION_TOKEN").ok();
+    Ok(Credentials {
+        access_key_id,
+        secret_access_key,
+        session_token,
+    })
+}
+
+fn from_instance_profile() -> Result<Credentials> {
+    let client = reqwest::blocking::Client::new();
+    let role = crate::aws::imds::get("/latest/meta-data/iam/security-credentials/", &client)?;
+    let role = role.lines().next().unwrap_or("").to_string();
+    if role.is_empty() {
+        return Err(format_err!("no IAM role attached to this instance"));
+    }
+    let body = crate::aws::imds::get(
+        &format!("/latest/meta-data/iam/security-credentials/{}", role),
+        &client,
+    )?;
+    let access_key_id = json_field(&body, "AccessKeyId")
+        .ok_or_else(|| format_err!("instance profile response missing AccessKeyId"))?;
+    let secret_access_key = json_field(&body, "SecretAccessKey")
+        .ok_or_else(|| format_err!("instance profile response missing SecretAccessKey"))?;
+    let session_token = json_field(&body, "Token");
+    Ok(Credentials {
+        access_key_id,
+        secret_access_key,
+        session_token,
+    })
+}
+
+/// Resolve credentials the way `WebIdentityProvider::from_k8s_env` used to:
+/// exchange the service-account token k8s projects into the pod for
+/// temporary credentials via STS, falling back to the regular chain when
+/// the pod isn't running with IRSA configured.
+pub fn from_k8s_web_identity_or_default(region: &str) -> Result<Credentials> {
+    match from_k8s_web_identity(region) {
+        Ok(creds) => Ok(creds),
+        Err(_) => from_env_or_instance_profile(),
+    }
+}
+
+fn from_k8s_web_identity(region: &str) -> Result<Credentials> {
+    let token_file = env::var("AWS_WEB_IDENTITY_TOKEN_FILE")
+        .map_err(|_| format_err!("AWS_WEB_IDENTITY_TOKEN_FILE not set"))?;
+    let role_arn = env::var("AWS_ROLE_ARN").map_err(|_| format_err!("AWS_ROLE_ARN not set"))?;
+    let token = std::fs::read_to_string(&token_file)
+        .map_err(|e| format_err!("failed to read {}: {}", token_file, e))?;
+    let token = token.trim();
+
+    let client = reqwest::blocking::Client::new();
+    let host = format!("sts.{}.amazonaws.com", region);
+    let response = client
+        .post(&format!("https://{}/", host))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(format!(
+            "Action=AssumeRoleWithWebIdentity&Version=2011-06-15&RoleArn={}&RoleSessionName=libra-cluster-test&WebIdentityToken={}",
+            percent_encode(&role_arn),
+            percent_encode(token),
+        ))
+        .send()
+        .map_err(|e| format_err!("AssumeRoleWithWebIdentity request failed: {}", e))?;
+    let body = response
+        .text()
+        .map_err(|e| format_err!("failed to read AssumeRoleWithWebIdentity response: {}", e))?;
+
+    let access_key_id = crate::aws::client::xml_field(&body, "AccessKeyId").ok_or_else(|| {
+        format_err!(
+            "AssumeRoleWithWebIdentity response missing AccessKeyId: {}",
+            body
+        )
+    })?;
+    let secret_access_key = crate::aws::client::xml_field(&body, "SecretAccessKey")
+        .ok_or_else(|| format_err!("AssumeRoleWithWebIdentity response missing SecretAccessKey"))?;
+    let session_token = crate::aws::client::xml_field(&body, "SessionToken");
+    Ok(Credentials {
+        access_key_id,
+        secret_access_key,
+        session_token,
+    })
+}
+
+/// Pull `"field": "value"` out of the small flat JSON document the instance
+/// metadata service returns, without pulling in a JSON parser for it.
+fn json_field(body: &str, field: &str) -> Option<String> {
+    let needle = format!("\"{}\"", field);
+    let idx = body.find(&needle)?;
+    let rest = &body[idx + needle.len()..];
+    let colon = rest.find(':')?;
+    let rest = rest[colon + 1..].trim_start();
+    let rest = rest.strip_prefix('"')?;
+    let end = rest.find('"')?;
+    Some(rest[..end].to_string())
+}
@@ -0,0 +1,43 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pluggable discovery of the cluster a `cluster-test` run belongs to.
+//! `Ec2Discovery` is always available (it's what `discover_workspace`
+//! already did); `K8sDiscovery` lives behind the `k8s-discovery` feature so
+//! non-k8s builds don't need to pull in an HTTP-based Kubernetes client.
+
+use crate::aws::Ec2Client;
+use anyhow::Result;
+
+/// What a `Discovery` backend knows about the cluster this process belongs
+/// to: the workspace/cluster name and the addresses of its peers.
+pub struct DiscoveredWorkspace {
+    pub workspace: String,
+    pub peers: Vec<String>,
+}
+
+/// A backend that can tell `cluster-test` which workspace it's running
+/// against and who else is in it.
+pub trait Discovery {
+    fn discover(&self) -> Result<DiscoveredWorkspace>;
+}
+
+/// Discover the workspace via the `Workspace` tag on the current EC2
+/// instance. This is the autoscaling-group-backed deployment model.
+pub struct Ec2Discovery {
+    pub(crate) ec2: Ec2Client,
+}
+
+impl Ec2Discovery {
+    pub fn new(ec2: Ec2Client) -> Self {
+        Self { ec2 }
+    }
+}
+
+impl Discovery for Ec2Discovery {
+    fn discover(&self) -> Result<DiscoveredWorkspace> {
+        let workspace = crate::aws::discover_workspace(&self.ec2);
+        let peers = self.ec2.describe_workspace_peers(&workspace)?;
+        Ok(DiscoveredWorkspace { workspace, peers })
+    }
+}
@@ -0,0 +1,22 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Percent-encoding shared by every piece of this crate that signs or
+//! builds a URL: SigV4 canonicalization, S3 request URLs, and the plain
+//! `application/x-www-form-urlencoded` bodies the Query protocol and STS
+//! use.
+
+/// Percent-encode `input` per RFC 3986 unreserved characters (used both for
+/// SigV4 canonical query strings/headers and for form-urlencoded bodies).
+pub(crate) fn percent_encode(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    result
+}
@@ -0,0 +1,80 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Minimal client for the EC2 instance metadata service (IMDS).
+
+use anyhow::{format_err, Result};
+
+const IMDS_HOST: &str = "http://169.254.169.254";
+const TOKEN_TTL_SECONDS: &str = "21600";
+
+/// Fetch an IMDSv2 session token, valid for `TOKEN_TTL_SECONDS`. Hosts that
+/// require session tokens reject unauthenticated metadata requests, so this
+/// must be called before `get_v2`.
+pub fn fetch_token(client: &reqwest::blocking::Client) -> Result<String> {
+    let response = client
+        .put(&format!("{}/latest/api/token", IMDS_HOST))
+        .header("X-aws-ec2-metadata-token-ttl-seconds", TOKEN_TTL_SECONDS)
+        .send()
+        .map_err(|e| format_err!("IMDSv2 token request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format_err!(
+            "IMDSv2 token request returned {}",
+            response.status()
+        ));
+    }
+    response
+        .text()
+        .map_err(|e| format_err!("failed to read IMDSv2 token response: {}", e))
+}
+
+/// Fetch `path` using an IMDSv2 session token.
+pub fn get_v2(path: &str, token: &str, client: &reqwest::blocking::Client) -> Result<String> {
+    let url = format!("{}{}", IMDS_HOST, path);
+    let response = client
+        .get(&url)
+        .header("X-aws-ec2-metadata-token", token)
+        .send()
+        .map_err(|e| format_err!("metadata request to {} failed: {}", path, e))?;
+    if !response.status().is_success() {
+        return Err(format_err!(
+            "metadata request to {} returned {}",
+            path,
+            response.status()
+        ));
+    }
+    response
+        .text()
+        .map_err(|e| format_err!("failed to read metadata response from {}: {}", path, e))
+}
+
+/// Fetch `path` (e.g. `/latest/meta-data/instance-id`) from the instance
+/// metadata service, preferring IMDSv2 and falling back to the tokenless
+/// IMDSv1 request when the token endpoint is unavailable (older instance
+/// configurations that don't require `IMDSv2`).
+pub fn get(path: &str, client: &reqwest::blocking::Client) -> Result<String> {
+    if let Ok(token) = fetch_token(client) {
+        if let Ok(body) = get_v2(path, &token, client) {
+            return Ok(body);
+        }
+    }
+    get_v1(path, client)
+}
+
+fn get_v1(path: &str, client: &reqwest::blocking::Client) -> Result<String> {
+    let url = format!("{}{}", IMDS_HOST, path);
+    let response = client
+        .get(&url)
+        .send()
+        .map_err(|e| format_err!("metadata request to {} failed: {}", path, e))?;
+    if !response.status().is_success() {
+        return Err(format_err!(
+            "metadata request to {} returned {}",
+            path,
+            response.status()
+        ));
+    }
+    response
+        .text()
+        .map_err(|e| format_err!("failed to read metadata response from {}: {}", path, e))
+}
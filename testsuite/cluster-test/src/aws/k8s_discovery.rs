@@ -0,0 +1,98 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Kubernetes-backed `Discovery`: list the peer pods of this run via the
+//! in-cluster Kubernetes API, the same way a pod would query the API server
+//! about its siblings with `kubectl`.
+
+use crate::aws::discovery::{DiscoveredWorkspace, Discovery};
+use crate::aws::encoding::percent_encode;
+use anyhow::{format_err, Result};
+use std::env;
+
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Discover cluster-test's peers by listing pods through the Kubernetes
+/// API, filtered to `label_selector` (e.g. `"app=libra-cluster-test"`).
+pub struct K8sDiscovery {
+    label_selector: String,
+}
+
+impl K8sDiscovery {
+    pub fn new(label_selector: &str) -> Self {
+        Self {
+            label_selector: label_selector.to_string(),
+        }
+    }
+}
+
+impl Discovery for K8sDiscovery {
+    fn discover(&self) -> Result<DiscoveredWorkspace> {
+        let host = env::var("KUBERNETES_SERVICE_HOST")
+            .map_err(|_| format_err!("KUBERNETES_SERVICE_HOST not set; not running in-cluster"))?;
+        let port = env::var("KUBERNETES_SERVICE_PORT_HTTPS").unwrap_or_else(|_| "443".to_string());
+        let token = std::fs::read_to_string(format!("{}/token", SERVICE_ACCOUNT_DIR))
+            .map_err(|e| format_err!("failed to read service account token: {}", e))?;
+        let namespace = std::fs::read_to_string(format!("{}/namespace", SERVICE_ACCOUNT_DIR))
+            .unwrap_or_else(|_| "default".to_string());
+        let ca_cert = std::fs::read(format!("{}/ca.crt", SERVICE_ACCOUNT_DIR))
+            .map_err(|e| format_err!("failed to read service account CA cert: {}", e))?;
+        let ca_cert = reqwest::Certificate::from_pem(&ca_cert)
+            .map_err(|e| format_err!("invalid service account CA cert: {}", e))?;
+
+        let http = reqwest::blocking::Client::builder()
+            .add_root_certificate(ca_cert)
+            .build()
+            .map_err(|e| format_err!("failed to build Kubernetes API client: {}", e))?;
+
+        let url = format!(
+            "https://{}:{}/api/v1/namespaces/{}/pods?labelSelector={}",
+            host,
+            port,
+            namespace.trim(),
+            percent_encode(&self.label_selector),
+        );
+        let response = http
+            .get(&url)
+            .bearer_auth(token.trim())
+            .send()
+            .map_err(|e| format_err!("Kubernetes API pod list request failed: {}", e))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .map_err(|e| format_err!("failed to read Kubernetes API response: {}", e))?;
+        if !status.is_success() {
+            return Err(format_err!(
+                "Kubernetes API pod list returned {}: {}",
+                status,
+                body
+            ));
+        }
+
+        Ok(DiscoveredWorkspace {
+            workspace: "k8s".to_string(),
+            peers: extract_json_string_fields(&body, "podIP"),
+        })
+    }
+}
+
+/// Pull every `"field": "value"` occurrence out of the Kubernetes API's JSON
+/// response, without pulling in a JSON parser for it.
+fn extract_json_string_fields(body: &str, field: &str) -> Vec<String> {
+    let needle = format!("\"{}\"", field);
+    let mut values = Vec::new();
+    let mut rest = body;
+    while let Some(idx) = rest.find(&needle) {
+        rest = &rest[idx + needle.len()..];
+        if let Some(value) = (|| -> Option<String> {
+            let colon = rest.find(':')?;
+            let after_colon = rest[colon + 1..].trim_start();
+            let after_quote = after_colon.strip_prefix('"')?;
+            let end = after_quote.find('"')?;
+            Some(after_quote[..end].to_string())
+        })() {
+            values.push(value);
+        }
+    }
+    values
+}
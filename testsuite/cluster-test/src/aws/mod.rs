@@ -0,0 +1,424 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#![forbid(unsafe_code)]
+
+use anyhow::{format_err, Result};
+use libra_logger::*;
+use reqwest::{self, Url};
+use std::{fs::File, io::Read, thread, time::Duration};
+use util::retry;
+
+mod client;
+mod credentials;
+pub mod discovery;
+mod encoding;
+mod imds;
+#[cfg(feature = "k8s-discovery")]
+mod k8s_discovery;
+mod sigv4;
+
+use client::AwsClient;
+use discovery::{Discovery, Ec2Discovery};
+#[cfg(feature = "k8s-discovery")]
+use k8s_discovery::K8sDiscovery;
+
+/// Label selector used to find this run's peers when discovering the
+/// workspace through the Kubernetes backend.
+const K8S_LABEL_SELECTOR: &str = "app=libra-cluster-test";
+
+const EC2_API_VERSION: &str = "2016-11-15";
+const AUTOSCALING_API_VERSION: &str = "2011-01-01";
+
+/// A handle to the EC2 API. Only the handful of calls this crate needs are
+/// implemented; everything else is left for whoever needs it next.
+#[derive(Clone)]
+pub struct Ec2Client {
+    client: std::sync::Arc<AwsClient>,
+}
+
+impl Ec2Client {
+    /// Describe `instance_ids`, returning every page of the (possibly
+    /// paginated) response.
+    fn describe_instances(&self, instance_ids: &[String]) -> Result<Vec<String>> {
+        let mut params = vec![
+            ("Action".to_string(), "DescribeInstances".to_string()),
+            ("Version".to_string(), EC2_API_VERSION.to_string()),
+        ];
+        for (i, id) in instance_ids.iter().enumerate() {
+            params.push((format!("InstanceId.{}", i + 1), id.clone()));
+        }
+        let params: Vec<(&str, &str)> = params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        self.client.query_paginated("ec2", &params)
+    }
+
+    /// Describe every running instance tagged `Workspace=workspace`,
+    /// returning each one's private IP address. Used by `Ec2Discovery` to
+    /// report this run's peers alongside its workspace name.
+    pub(crate) fn describe_workspace_peers(&self, workspace: &str) -> Result<Vec<String>> {
+        let params = [
+            ("Action", "DescribeInstances"),
+            ("Version", EC2_API_VERSION),
+            ("Filter.1.Name", "tag:Workspace"),
+            ("Filter.1.Value.1", workspace),
+            ("Filter.2.Name", "instance-state-name"),
+            ("Filter.2.Value.1", "running"),
+        ];
+        let pages = self.client.query_paginated("ec2", &params)?;
+        let mut peers = Vec::new();
+        for page in &pages {
+            for reservation in client::xml_blocks(page, "item") {
+                // A reservation's `item`s aren't only instances: `groupSet`
+                // holds security-group `item`s at the same nesting depth.
+                // Scope to `instancesSet` first so we only ever see instance
+                // blocks here.
+                let instances_set =
+                    client::xml_field(reservation, "instancesSet").unwrap_or_default();
+                for instance in client::xml_blocks(&instances_set, "item") {
+                    if let Some(ip) = client::xml_field(instance, "privateIpAddress") {
+                        peers.push(ip);
+                    }
+                }
+            }
+        }
+        Ok(peers)
+    }
+}
+
+/// A handle to the ECR API, kept around for callers that need to push/pull
+/// images; this crate itself doesn't call into it directly.
+#[derive(Clone)]
+pub struct EcrClient {
+    #[allow(dead_code)]
+    client: std::sync::Arc<AwsClient>,
+}
+
+/// A handle to the ECS API, kept around for callers that manage services;
+/// this crate itself doesn't call into it directly.
+#[derive(Clone)]
+pub struct EcsClient {
+    #[allow(dead_code)]
+    client: std::sync::Arc<AwsClient>,
+}
+
+#[derive(Clone)]
+pub struct Aws {
+    workspace: String,
+    region: String,
+    ec2: Ec2Client,
+    ecr: EcrClient,
+    ecs: EcsClient,
+}
+
+impl Aws {
+    pub fn new(k8s: bool) -> Self {
+        Self::new_with_region(k8s, None)
+    }
+
+    pub fn new_with_region(k8s: bool, region: Option<String>) -> Self {
+        let region = client::resolve_region(region.as_deref());
+        // On the k8s discovery path these clients are never actually used
+        // (workspace/peers come from the Kubernetes API instead), so a pod
+        // running under IRSA with no static AWS_* env vars and no reachable
+        // instance metadata service shouldn't panic just for carrying them.
+        let client = if k8s {
+            AwsClient::new_best_effort(&region)
+        } else {
+            AwsClient::new(&region).expect("failed to resolve AWS credentials for EC2 client")
+        };
+        let client = std::sync::Arc::new(client);
+        let ec2 = Ec2Client {
+            client: client.clone(),
+        };
+        let workspace = discover(k8s, &ec2);
+        Self {
+            workspace,
+            region,
+            ec2,
+            ecr: EcrClient {
+                client: client.clone(),
+            },
+            ecs: EcsClient { client },
+        }
+    }
+
+    pub fn ec2(&self) -> &Ec2Client {
+        &self.ec2
+    }
+
+    pub fn ecr(&self) -> &EcrClient {
+        &self.ecr
+    }
+
+    pub fn ecs(&self) -> &EcsClient {
+        &self.ecs
+    }
+
+    pub fn workspace(&self) -> &String {
+        &self.workspace
+    }
+
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+}
+
+impl Default for Aws {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+/// Resolve the workspace via whichever `Discovery` backend fits how this
+/// process was deployed: the k8s backend when running in a cluster, the EC2
+/// autoscaling-group backend otherwise.
+fn discover(k8s: bool, ec2: &Ec2Client) -> String {
+    if k8s {
+        discover_k8s()
+    } else {
+        Ec2Discovery::new(ec2.clone())
+            .discover()
+            .expect("EC2 workspace discovery failed")
+            .workspace
+    }
+}
+
+#[cfg(feature = "k8s-discovery")]
+fn discover_k8s() -> String {
+    K8sDiscovery::new(K8S_LABEL_SELECTOR)
+        .discover()
+        .expect("Kubernetes workspace discovery failed")
+        .workspace
+}
+
+/// Built without the `k8s-discovery` feature: the in-cluster discovery
+/// backend isn't compiled in, so fall back to the literal workspace name the
+/// k8s deployment model has always used.
+#[cfg(not(feature = "k8s-discovery"))]
+fn discover_k8s() -> String {
+    "k8s".to_string()
+}
+
+fn discover_workspace(ec2: &Ec2Client) -> String {
+    let instance_id = current_instance_id();
+    let mut attempt = 0;
+    loop {
+        let pages = match ec2.describe_instances(&[instance_id.clone()]) {
+            Ok(pages) => pages,
+            Err(e) => {
+                attempt += 1;
+                if attempt > 10 {
+                    panic!("Failed to discover workspace");
+                }
+                error!(
+                    "Transient failure when discovering workspace(attempt {}): {}",
+                    attempt, e
+                );
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+        for page in &pages {
+            for reservation in client::xml_blocks(page, "item") {
+                // Same instancesSet-scoping as describe_workspace_peers:
+                // a reservation's `item`s also include groupSet's
+                // security-group entries at the same nesting depth.
+                let instances_set =
+                    client::xml_field(reservation, "instancesSet").unwrap_or_default();
+                for instance in client::xml_blocks(&instances_set, "item") {
+                    let tags_block = client::xml_field(instance, "tagSet").unwrap_or_default();
+                    for tag in client::xml_blocks(&tags_block, "item") {
+                        if client::xml_field(tag, "key").as_deref() == Some("Workspace") {
+                            return client::xml_field(tag, "value")
+                                .expect("discover_workspace: no tag value");
+                        }
+                    }
+                }
+            }
+        }
+        panic!(
+            "discover_workspace: no workspace tag on any instance. Instance id: {}",
+            instance_id
+        );
+    }
+}
+
+fn current_instance_id() -> String {
+    let client = reqwest::blocking::Client::new();
+    // Prefer IMDSv2: hosts configured to require session tokens reject the
+    // tokenless IMDSv1 request outright.
+    if let Ok(token) = imds::fetch_token(&client) {
+        if let Ok(id) = imds::get_v2("/latest/meta-data/instance-id", &token, &client) {
+            return id;
+        }
+    }
+    let url = Url::parse("http://169.254.169.254/1.0/meta-data/instance-id");
+    let url = url.expect("Failed to parse metadata url");
+    let response = client.get(url).send();
+    let response = response.expect("Metadata request failed");
+    response.text().expect("Failed to parse metadata response")
+}
+
+pub fn autoscale(desired_capacity: i64, asg_name: &str, region: Option<&str>) -> Result<()> {
+    let region = client::resolve_region(region);
+    let creds = credentials::from_k8s_web_identity_or_default(&region)?;
+    let asc = AwsClient::with_credentials(&region, creds);
+    asc.query(
+        "autoscaling",
+        &[
+            ("Action", "SetDesiredCapacity"),
+            ("Version", AUTOSCALING_API_VERSION),
+            ("AutoScalingGroupName", asg_name),
+            ("DesiredCapacity", &desired_capacity.to_string()),
+            ("HonorCooldown", "false"),
+        ],
+    )
+    .map_err(|e| format_err!("set_desired_capacity failed: {:?}", e))?;
+    retry::retry(retry::fixed_retry_strategy(10_000, 30), || {
+        let pages = asc.query_paginated(
+            "autoscaling",
+            &[
+                ("Action", "DescribeAutoScalingGroups"),
+                ("Version", AUTOSCALING_API_VERSION),
+                ("AutoScalingGroupNames.member.1", asg_name),
+            ],
+        )?;
+        let mut count = 0i64;
+        let mut found_group = false;
+        for page in &pages {
+            for group in client::xml_blocks(page, "member") {
+                let instances_block = match client::xml_field(group, "Instances") {
+                    Some(instances_block) => instances_block,
+                    None => continue,
+                };
+                found_group = true;
+                count += client::xml_blocks(&instances_block, "member")
+                    .into_iter()
+                    .filter(|instance| {
+                        client::xml_field(instance, "LifecycleState").as_deref()
+                            == Some("InService")
+                    })
+                    .count() as i64;
+            }
+        }
+        if !found_group {
+            return Err(format_err!(
+                "instances not found for auto_scaling_group {}",
+                asg_name
+            ));
+        }
+        if count < desired_capacity {
+            info!(
+                "Waiting for scale-up to complete. Current size: {}, Desired Size: {}",
+                count, desired_capacity
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Above this size, `upload_to_s3` switches from a single `PutObject` to a
+/// multipart upload so no single request has to hold the whole file.
+const MULTIPART_THRESHOLD_BYTES: u64 = 100 * 1024 * 1024;
+/// Every part but the last must be at least 5 MB per the S3 multipart API.
+const MULTIPART_PART_SIZE_BYTES: usize = 8 * 1024 * 1024;
+
+pub fn upload_to_s3(
+    local_filename: &str,
+    bucket: &str,
+    dest_filename: &str,
+    content_type: Option<String>,
+    region: Option<&str>,
+) -> Result<()> {
+    let file = File::open(local_filename).unwrap();
+    let size = file
+        .metadata()
+        .map_err(|e| format_err!("Error opening file to send to S3: {}", e))?
+        .len();
+    let region = client::resolve_region(region);
+    let client = AwsClient::new(&region)
+        .map_err(|e| format_err!("failed to resolve AWS credentials for S3: {}", e))?;
+
+    if size > MULTIPART_THRESHOLD_BYTES {
+        upload_to_s3_multipart(&client, file, bucket, dest_filename, content_type)
+    } else {
+        client::s3_put_object(&client, bucket, dest_filename, file, size, content_type)
+            .map_err(|e| format_err!("Failed to upload to S3: {:?}", e))
+    }
+}
+
+fn upload_to_s3_multipart(
+    client: &AwsClient,
+    mut file: File,
+    bucket: &str,
+    dest_filename: &str,
+    content_type: Option<String>,
+) -> Result<()> {
+    let upload_id = client::s3_create_multipart_upload(client, bucket, dest_filename, content_type)
+        .map_err(|e| format_err!("Failed to start multipart upload to S3: {:?}", e))?;
+
+    let mut parts = Vec::new();
+    let mut buf = vec![0u8; MULTIPART_PART_SIZE_BYTES];
+    let mut part_number = 1u32;
+    let result = loop {
+        let filled = match read_full_or_eof(&mut file, &mut buf) {
+            Ok(filled) => filled,
+            Err(e) => break Err(format_err!("Error reading {}: {}", dest_filename, e)),
+        };
+        if filled == 0 {
+            break Ok(());
+        }
+        match client::s3_upload_part(
+            client,
+            bucket,
+            dest_filename,
+            &upload_id,
+            part_number,
+            buf[..filled].to_vec(),
+        ) {
+            Ok(etag) => parts.push((part_number, etag)),
+            Err(e) => {
+                break Err(format_err!(
+                    "Failed to upload part {}: {:?}",
+                    part_number,
+                    e
+                ))
+            }
+        }
+        part_number += 1;
+    };
+
+    match result {
+        Ok(()) => {
+            client::s3_complete_multipart_upload(client, bucket, dest_filename, &upload_id, &parts)
+                .map_err(|e| format_err!("Failed to complete multipart upload to S3: {:?}", e))
+        }
+        Err(e) => {
+            if let Err(abort_err) =
+                client::s3_abort_multipart_upload(client, bucket, dest_filename, &upload_id)
+            {
+                error!(
+                    "Failed to abort multipart upload {} after error: {}",
+                    upload_id, abort_err
+                );
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Fill `buf` from `file`, stopping early at EOF. Returns how much of `buf`
+/// was actually filled.
+fn read_full_or_eof(file: &mut File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
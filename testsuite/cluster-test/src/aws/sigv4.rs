@@ -0,0 +1,164 @@
+// Copyright (c) The Libra Core Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! A minimal implementation of AWS Signature Version 4, just enough to sign
+//! the EC2, Autoscaling and S3 requests this crate makes. See
+//! https://docs.aws.amazon.com/general/latest/gr/sigv4-create-canonical-request.html
+
+use crate::aws::encoding::percent_encode;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Clone)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// A signed request, ready to be sent over the wire.
+pub struct SignedRequest {
+    pub headers: Vec<(String, String)>,
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hex::encode(hasher.finalize())
+}
+
+fn signing_key(secret_access_key: &str, date_stamp: &str, region: &str, service: &str) -> Vec<u8> {
+    let k_date = hmac_sha256(
+        format!("AWS4{}", secret_access_key).as_bytes(),
+        date_stamp.as_bytes(),
+    );
+    let k_region = hmac_sha256(&k_date, region.as_bytes());
+    let k_service = hmac_sha256(&k_region, service.as_bytes());
+    hmac_sha256(&k_service, b"aws4_request")
+}
+
+/// Sign a request for `service` in `region`, returning the headers that must
+/// be added to the request (`x-amz-date`, `x-amz-content-sha256`,
+/// `x-amz-security-token` when the credentials carry a session token, and
+/// `Authorization`). `host` and `extra_headers` are signed but are not among
+/// the returned headers.
+///
+/// `query_params` and `extra_headers` must contain every query parameter and
+/// header that will actually be sent, since they are part of what gets
+/// signed.
+#[allow(clippy::too_many_arguments)]
+pub fn sign(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    host: &str,
+    canonical_uri: &str,
+    query_params: &BTreeMap<String, String>,
+    extra_headers: &[(String, String)],
+    payload: &[u8],
+    amz_date: &str,
+) -> SignedRequest {
+    sign_with_payload_hash(
+        credentials,
+        region,
+        service,
+        method,
+        host,
+        canonical_uri,
+        query_params,
+        extra_headers,
+        &sha256_hex(payload),
+        amz_date,
+    )
+}
+
+/// Like `sign`, but takes an already-computed payload hash instead of the
+/// payload itself. S3 accepts the literal `UNSIGNED-PAYLOAD` here, which
+/// lets callers stream a request body without buffering it to compute a
+/// SHA256 up front.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_with_payload_hash(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    host: &str,
+    canonical_uri: &str,
+    query_params: &BTreeMap<String, String>,
+    extra_headers: &[(String, String)],
+    payload_hash: &str,
+    amz_date: &str,
+) -> SignedRequest {
+    let date_stamp = &amz_date[..8];
+    let payload_hash = payload_hash.to_string();
+
+    let canonical_query_string = query_params
+        .iter()
+        .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut headers: BTreeMap<String, String> = BTreeMap::new();
+    headers.insert("host".to_string(), host.to_string());
+    headers.insert("x-amz-date".to_string(), amz_date.to_string());
+    headers.insert("x-amz-content-sha256".to_string(), payload_hash.clone());
+    if let Some(token) = &credentials.session_token {
+        headers.insert("x-amz-security-token".to_string(), token.clone());
+    }
+    for (k, v) in extra_headers {
+        headers.insert(k.to_lowercase(), v.clone());
+    }
+
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+    let canonical_headers = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+        .collect::<String>();
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        canonical_uri,
+        canonical_query_string,
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+
+    let credential_scope = format!("{}/{}/{}/aws4_request", date_stamp, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        credential_scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let key = signing_key(&credentials.secret_access_key, date_stamp, region, service);
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, credential_scope, signed_headers, signature
+    );
+
+    let mut result_headers: Vec<(String, String)> = headers
+        .into_iter()
+        .filter(|(k, _)| {
+            k != "host" && !extra_headers.iter().any(|(name, _)| name.to_lowercase() == *k)
+        })
+        .collect();
+    result_headers.push(("Authorization".to_string(), authorization));
+    SignedRequest {
+        headers: result_headers,
+    }
+}